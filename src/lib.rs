@@ -1,28 +1,49 @@
-use std::collections::HashMap;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
 
 pub use bevy;
 pub mod prelude {
-    pub use crate::{getters::*, Config, EventHandler};
-    pub use bevy::{input::ElementState, math::vec2, prelude::*};
+    pub use crate::{
+        getters::*,
+        render::{
+            draw_circle, draw_circle_indexed, draw_line, draw_line_indexed, draw_rect,
+            draw_rect_indexed, draw_sprite, draw_sprite_indexed, draw_text, draw_text_indexed,
+        },
+        Bindings, Config, EventHandler, ModifiersState, Trigger,
+    };
+    pub use bevy::{
+        input::{mouse::MouseScrollUnit, ElementState},
+        math::vec2,
+        prelude::*,
+    };
 }
-mod render;
+pub mod render;
+pub use render::RenderId;
 
 use bevy::{
     input::{
+        gamepad::{
+            Gamepad, GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType,
+            GamepadEvent, GamepadEventType,
+        },
         keyboard::KeyboardInput,
-        mouse::{MouseButtonInput, MouseMotion},
+        mouse::{MouseButtonInput, MouseMotion, MouseScrollUnit, MouseWheel},
     },
-    window::{WindowCloseRequested, WindowResized},
+    window::{WindowCloseRequested, WindowFocused, WindowResized},
 };
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 use prelude::*;
 
 pub trait EventHandler: Send + Sync + 'static {
-    fn config() -> Config {
-        Config {
-            window: WindowDescriptor::default(),
-        }
+    /// The user-named action type produced by the keybinding table. Set this to
+    /// `&'static str` or `()` when no bindings are used.
+    type Action: Copy + Eq + Hash + Send + Sync + 'static;
+    fn config() -> Config<Self::Action> {
+        Config::new()
     }
     fn init_app(_app: &mut App) {}
     fn update(&mut self, dt: f32);
@@ -30,7 +51,11 @@ pub trait EventHandler: Send + Sync + 'static {
     fn keyboard(&mut self, _key: KeyCode, _scan_code: u32, _state: ElementState, _repeat: bool) {}
     fn mouse_button(&mut self, _button: MouseButton, _state: ElementState) {}
     fn mouse_relative(&mut self, _delta: Vec2) {}
+    fn mouse_wheel(&mut self, _delta: Vec2, _unit: MouseScrollUnit) {}
     fn mouse_absolute(&mut self, _pos: Vec2) {}
+    fn action(&mut self, _action: Self::Action, _state: ElementState) {}
+    fn gamepad_button(&mut self, _button: GamepadButton, _state: ElementState) {}
+    fn gamepad_axis(&mut self, _axis: GamepadAxis, _value: f32) {}
     fn window_resized(&mut self, _new_size: Vec2) {}
     fn close_requested(&mut self) -> bool {
         true
@@ -40,20 +65,33 @@ pub trait EventHandler: Send + Sync + 'static {
 struct Context {
     window_size: Vec2,
     mouse_position: Vec2,
-    keys: HashMap<KeyCode, ButtonState>,
-    mouse_buttons: HashMap<MouseButton, ButtonState>,
+    mouse_scroll: Vec2,
+    modifiers: ModifiersState,
+    keys: ButtonInput<KeyCode>,
+    mouse_buttons: ButtonInput<MouseButton>,
+    gamepad_buttons: ButtonInput<GamepadButton>,
+    gamepad_axes: HashMap<GamepadAxis, f32>,
+    /// Currently-down actions, boxed as `HashSet<A>` for the concrete action
+    /// type of the running [`EventHandler`].
+    actions: Box<dyn Any + Send + Sync>,
 }
 
 pub fn run<T>(state: T)
 where
     T: EventHandler,
 {
-    let config = T::config();
+    let mut config = T::config();
+    let bindings = std::mem::take(&mut config.bindings);
     *CONTEXT.write() = Some(Context {
         window_size: vec2(config.window.width, config.window.height),
         mouse_position: Vec2::ZERO,
-        keys: HashMap::new(),
-        mouse_buttons: HashMap::new(),
+        mouse_scroll: Vec2::ZERO,
+        modifiers: ModifiersState::NONE,
+        keys: ButtonInput::default(),
+        mouse_buttons: ButtonInput::default(),
+        gamepad_buttons: ButtonInput::default(),
+        gamepad_axes: HashMap::new(),
+        actions: Box::new(HashSet::<T::Action>::new()),
     });
 
     let mut app = App::new();
@@ -63,10 +101,16 @@ where
     })
     .add_plugins(DefaultPlugins)
     .insert_resource(state)
+    .insert_resource(bindings)
+    .insert_resource(render::RenderState::new(config.font))
+    .add_startup_system(spawn_cameras)
     .add_system(update_keys::<T>)
     .add_system(move |time: Res<Time>, mut state: ResMut<T>| {
         T::update(&mut *state, time.delta_seconds());
-    });
+    })
+    .add_system(draw::<T>.label(DrawLabel))
+    .add_system(render::flush_render.after(DrawLabel))
+    .add_system_to_stage(CoreStage::Last, clear_input);
 
     T::init_app(&mut app);
 
@@ -75,17 +119,241 @@ where
     *CONTEXT.write() = None;
 }
 
-pub struct Config {
+pub struct Config<A> {
     window: WindowDescriptor,
+    bindings: Bindings<A>,
+    font: Option<String>,
+}
+
+impl<A> Config<A> {
+    pub fn new() -> Self {
+        Config {
+            window: WindowDescriptor::default(),
+            bindings: Bindings::default(),
+            font: None,
+        }
+    }
+    /// Set the window the app opens with.
+    pub fn window(mut self, window: WindowDescriptor) -> Self {
+        self.window = window;
+        self
+    }
+    /// Set the font asset path used by [`draw_text`](render::draw_text), loaded
+    /// from the consuming app's asset folder. Text is not rendered until a font
+    /// is set.
+    pub fn font(mut self, path: impl Into<String>) -> Self {
+        self.font = Some(path.into());
+        self
+    }
+    /// Register the action keybinding table.
+    pub fn bindings(mut self, bindings: Bindings<A>) -> Self {
+        self.bindings = bindings;
+        self
+    }
+}
+
+impl<A> Default for Config<A> {
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
+/// Something a [`Binding`] can be triggered by.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl From<KeyCode> for Trigger {
+    fn from(key: KeyCode) -> Self {
+        Trigger::Key(key)
+    }
+}
+
+impl From<MouseButton> for Trigger {
+    fn from(button: MouseButton) -> Self {
+        Trigger::Mouse(button)
+    }
+}
+
+/// The set of keyboard modifiers held for a chord. Matching is exact: a binding
+/// only fires when the live modifier state equals its required state.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ModifiersState {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl ModifiersState {
+    pub const NONE: ModifiersState = ModifiersState {
+        shift: false,
+        ctrl: false,
+        alt: false,
+        logo: false,
+    };
+    pub fn with_shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+    pub fn with_ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+    pub fn with_alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+    pub fn with_logo(mut self) -> Self {
+        self.logo = true;
+        self
+    }
+    /// Update the modifier matching `key`, returning `true` if `key` was a
+    /// modifier.
+    fn set(&mut self, key: KeyCode, down: bool) -> bool {
+        match key {
+            KeyCode::LShift | KeyCode::RShift => self.shift = down,
+            KeyCode::LControl | KeyCode::RControl => self.ctrl = down,
+            KeyCode::LAlt | KeyCode::RAlt => self.alt = down,
+            KeyCode::LWin | KeyCode::RWin => self.logo = down,
+            _ => return false,
+        }
+        true
+    }
+}
+
+struct Binding<A> {
+    trigger: Trigger,
+    modifiers: ModifiersState,
+    action: A,
+}
+
+/// A builder-style table mapping trigger+modifier chords to user-named actions.
+pub struct Bindings<A> {
+    entries: Vec<Binding<A>>,
+}
+
+impl<A> Default for Bindings<A> {
+    fn default() -> Self {
+        Bindings {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<A> Bindings<A> {
+    pub fn new() -> Self {
+        Bindings::default()
+    }
+    /// Bind a trigger held with exactly `modifiers` to `action`.
+    pub fn bind(
+        mut self,
+        trigger: impl Into<Trigger>,
+        modifiers: ModifiersState,
+        action: A,
+    ) -> Self {
+        self.entries.push(Binding {
+            trigger: trigger.into(),
+            modifiers,
+            action,
+        });
+        self
+    }
+}
+
+#[derive(SystemLabel, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct DrawLabel;
+
+fn spawn_cameras(mut commands: Commands) {
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+fn draw<T: EventHandler>(mut state: ResMut<T>) {
+    T::draw(&mut *state);
+}
+
+/// Clear the per-frame just-pressed/just-released sets once everything else has
+/// had a chance to observe them this frame.
+fn clear_input(mut focus: EventReader<WindowFocused>) {
+    let lost_focus = focus.iter().any(|event| !event.focused);
+    ctx_mut(|ctx| {
+        ctx.keys.clear();
+        ctx.mouse_buttons.clear();
+        ctx.gamepad_buttons.clear();
+        ctx.mouse_scroll = Vec2::ZERO;
+        if lost_focus {
+            ctx.keys.release_all();
+            ctx.mouse_buttons.release_all();
+            ctx.gamepad_buttons.release_all();
+        }
+    });
 }
 
-#[derive(Default)]
-struct ButtonState {
-    down: bool,
-    pressed: bool,
-    released: bool,
+/// Per-frame button tracking for an arbitrary set of inputs, mirroring bevy's
+/// own `Input<T>`: `pressed` holds everything currently held down, while
+/// `just_pressed`/`just_released` hold only the transitions from this frame and
+/// are cleared at the end of it.
+struct ButtonInput<T> {
+    pressed: HashSet<T>,
+    just_pressed: HashSet<T>,
+    just_released: HashSet<T>,
 }
 
+impl<T> Default for ButtonInput<T> {
+    fn default() -> Self {
+        ButtonInput {
+            pressed: HashSet::new(),
+            just_pressed: HashSet::new(),
+            just_released: HashSet::new(),
+        }
+    }
+}
+
+impl<T> ButtonInput<T>
+where
+    T: Copy + Eq + Hash,
+{
+    fn press(&mut self, input: T) {
+        if self.pressed.insert(input) {
+            self.just_pressed.insert(input);
+        }
+    }
+    fn release(&mut self, input: T) {
+        if self.pressed.remove(&input) {
+            self.just_released.insert(input);
+        }
+    }
+    fn down(&self, input: T) -> bool {
+        self.pressed.contains(&input)
+    }
+    fn pressed(&self, input: T) -> bool {
+        self.just_pressed.contains(&input)
+    }
+    fn released(&self, input: T) -> bool {
+        self.just_released.contains(&input)
+    }
+    /// Clear the per-frame transition sets, called at the end of every frame.
+    fn clear(&mut self) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+    }
+    /// Release every held input, e.g. when the window loses focus.
+    fn release_all(&mut self) {
+        for input in self.pressed.drain() {
+            self.just_released.insert(input);
+        }
+    }
+}
+
+/// Pixels assumed per line of line-based scroll, used to normalize wheel deltas.
+const LINE_TO_PIXELS: f32 = 20.0;
+
+/// Analog value at or above which a gamepad button counts as pressed.
+const GAMEPAD_BUTTON_THRESHOLD: f32 = 0.75;
+
 static CONTEXT: Lazy<RwLock<Option<Context>>> = Lazy::new(Default::default);
 
 fn ctx<F, T>(mut f: F) -> T
@@ -112,33 +380,122 @@ mod getters {
         ctx(|ctx| ctx.mouse_position)
     }
 
+    /// Scroll accumulated this frame, normalized to pixels.
+    pub fn mouse_scroll() -> Vec2 {
+        ctx(|ctx| ctx.mouse_scroll)
+    }
+
     pub fn is_key_down(key: KeyCode) -> bool {
-        ctx(|ctx| ctx.keys.get(&key).map_or(false, |s| s.down))
+        ctx(|ctx| ctx.keys.down(key))
     }
     pub fn is_key_pressed(key: KeyCode) -> bool {
-        ctx(|ctx| ctx.keys.get(&key).map_or(false, |s| s.pressed))
+        ctx(|ctx| ctx.keys.pressed(key))
     }
     pub fn is_key_released(key: KeyCode) -> bool {
-        ctx(|ctx| ctx.keys.get(&key).map_or(false, |s| s.released))
+        ctx(|ctx| ctx.keys.released(key))
+    }
+
+    /// All keys currently held down.
+    pub fn get_pressed() -> impl Iterator<Item = KeyCode> {
+        ctx(|ctx| ctx.keys.pressed.iter().copied().collect::<Vec<_>>()).into_iter()
+    }
+    /// Keys that were pressed this frame.
+    pub fn get_just_pressed() -> impl Iterator<Item = KeyCode> {
+        ctx(|ctx| ctx.keys.just_pressed.iter().copied().collect::<Vec<_>>()).into_iter()
+    }
+    /// Whether any of the given keys are currently held down.
+    pub fn any_pressed(keys: impl IntoIterator<Item = KeyCode>) -> bool {
+        keys.into_iter().any(is_key_down)
+    }
+    /// Whether any of the given keys were pressed this frame.
+    pub fn any_just_pressed(keys: impl IntoIterator<Item = KeyCode>) -> bool {
+        keys.into_iter().any(is_key_pressed)
+    }
+
+    /// Whether `action` is currently bound-and-held.
+    pub fn is_action_down<A>(action: A) -> bool
+    where
+        A: Copy + Eq + Hash + 'static,
+    {
+        ctx(|ctx| {
+            ctx.actions
+                .downcast_ref::<HashSet<A>>()
+                .map_or(false, |actions| actions.contains(&action))
+        })
+    }
+
+    pub fn is_gamepad_button_down(pad: Gamepad, button: GamepadButtonType) -> bool {
+        ctx(|ctx| ctx.gamepad_buttons.down(GamepadButton(pad, button)))
+    }
+    pub fn gamepad_axis(pad: Gamepad, axis: GamepadAxisType) -> f32 {
+        ctx(|ctx| {
+            ctx.gamepad_axes
+                .get(&GamepadAxis(pad, axis))
+                .copied()
+                .unwrap_or(0.0)
+        })
     }
 
     pub fn is_mouse_button_down(mb: MouseButton) -> bool {
-        ctx(|ctx| ctx.mouse_buttons.get(&mb).map_or(false, |s| s.down))
+        ctx(|ctx| ctx.mouse_buttons.down(mb))
     }
     pub fn is_mouse_button_pressed(mb: MouseButton) -> bool {
-        ctx(|ctx| ctx.mouse_buttons.get(&mb).map_or(false, |s| s.pressed))
+        ctx(|ctx| ctx.mouse_buttons.pressed(mb))
     }
     pub fn is_mouse_button_released(mb: MouseButton) -> bool {
-        ctx(|ctx| ctx.mouse_buttons.get(&mb).map_or(false, |s| s.released))
+        ctx(|ctx| ctx.mouse_buttons.released(mb))
     }
 }
 pub use getters::*;
 
+fn fire_bindings<T: EventHandler>(
+    state: &mut T,
+    bindings: &Bindings<T::Action>,
+    trigger: Trigger,
+    element_state: ElementState,
+) {
+    let modifiers = ctx(|ctx| ctx.modifiers);
+    for binding in &bindings.entries {
+        if binding.trigger != trigger {
+            continue;
+        }
+        let fired = match element_state {
+            // A chord only *fires* when its modifiers are exactly satisfied.
+            ElementState::Pressed => {
+                if binding.modifiers != modifiers {
+                    continue;
+                }
+                ctx_mut(|ctx| {
+                    ctx.actions
+                        .downcast_mut::<HashSet<T::Action>>()
+                        .map_or(false, |actions| actions.insert(binding.action))
+                })
+            }
+            // Release whenever the *trigger* matches, regardless of the live
+            // modifier state: the modifier keys are commonly released before the
+            // trigger (e.g. Ctrl up, then S up), so requiring the chord to still
+            // be satisfied would strand the action down forever. The action set
+            // doubles as the record of which bindings are currently active.
+            ElementState::Released => ctx_mut(|ctx| {
+                ctx.actions
+                    .downcast_mut::<HashSet<T::Action>>()
+                    .map_or(false, |actions| actions.remove(&binding.action))
+            }),
+        };
+        if fired {
+            state.action(binding.action, element_state);
+        }
+    }
+}
+
 fn update_keys<T: EventHandler>(
     mut state: ResMut<T>,
+    bindings: Res<Bindings<T::Action>>,
     mut keyboard: EventReader<KeyboardInput>,
     mut mouse_buttons: EventReader<MouseButtonInput>,
     mut mouse_motion: EventReader<MouseMotion>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut gamepad: EventReader<GamepadEvent>,
     mut cursor_motion: EventReader<CursorMoved>,
     mut resize: EventReader<WindowResized>,
     close: EventReader<WindowCloseRequested>,
@@ -149,22 +506,17 @@ fn update_keys<T: EventHandler>(
             if let Some(key) = input.key_code {
                 let mut repeat = false;
                 ctx_mut(|ctx| {
-                    let key_state = ctx.keys.entry(key).or_default();
-                    key_state.pressed = false;
-                    key_state.released = false;
+                    ctx.modifiers.set(key, input.state == ElementState::Pressed);
                     match input.state {
                         ElementState::Pressed => {
-                            repeat = key_state.down;
-                            key_state.down = true;
-                            key_state.pressed = true;
-                        }
-                        ElementState::Released => {
-                            key_state.down = false;
-                            key_state.released = true
+                            repeat = ctx.keys.down(key);
+                            ctx.keys.press(key);
                         }
+                        ElementState::Released => ctx.keys.release(key),
                     }
                 });
                 state.keyboard(key, input.scan_code, input.state, repeat);
+                fire_bindings(&mut *state, &bindings, Trigger::Key(key), input.state);
             }
         }
     }
@@ -172,22 +524,12 @@ fn update_keys<T: EventHandler>(
     // Mouse buttons
     if !mouse_buttons.is_empty() {
         for input in mouse_buttons.iter() {
-            ctx_mut(|ctx| {
-                let button_state = ctx.mouse_buttons.entry(input.button).or_default();
-                button_state.pressed = false;
-                button_state.released = false;
-                match input.state {
-                    ElementState::Pressed => {
-                        button_state.down = true;
-                        button_state.pressed = true;
-                    }
-                    ElementState::Released => {
-                        button_state.down = false;
-                        button_state.released = true
-                    }
-                }
+            ctx_mut(|ctx| match input.state {
+                ElementState::Pressed => ctx.mouse_buttons.press(input.button),
+                ElementState::Released => ctx.mouse_buttons.release(input.button),
             });
             state.mouse_button(input.button, input.state);
+            fire_bindings(&mut *state, &bindings, Trigger::Mouse(input.button), input.state);
         }
     }
 
@@ -198,6 +540,72 @@ fn update_keys<T: EventHandler>(
         }
     }
 
+    // Mouse wheel
+    if !mouse_wheel.is_empty() {
+        for wheel in mouse_wheel.iter() {
+            let delta = vec2(wheel.x, wheel.y);
+            // Line-based deltas (mice) come in whole notches while pixel-based
+            // deltas (trackpads) are already fine-grained; normalize the former
+            // to pixels so callers see consistent magnitudes across platforms.
+            let normalized = match wheel.unit {
+                MouseScrollUnit::Line => delta * LINE_TO_PIXELS,
+                MouseScrollUnit::Pixel => delta,
+            };
+            ctx_mut(|ctx| ctx.mouse_scroll += normalized);
+            state.mouse_wheel(delta, wheel.unit);
+        }
+    }
+
+    // Gamepad
+    if !gamepad.is_empty() {
+        for GamepadEvent(pad, event) in gamepad.iter() {
+            match event {
+                GamepadEventType::ButtonChanged(button_type, value) => {
+                    let button = GamepadButton(*pad, *button_type);
+                    let down = *value >= GAMEPAD_BUTTON_THRESHOLD;
+                    let changed = ctx_mut(|ctx| {
+                        let was_down = ctx.gamepad_buttons.down(button);
+                        if down {
+                            ctx.gamepad_buttons.press(button);
+                        } else {
+                            ctx.gamepad_buttons.release(button);
+                        }
+                        down != was_down
+                    });
+                    if changed {
+                        let element_state = if down {
+                            ElementState::Pressed
+                        } else {
+                            ElementState::Released
+                        };
+                        state.gamepad_button(button, element_state);
+                    }
+                }
+                GamepadEventType::AxisChanged(axis_type, value) => {
+                    let axis = GamepadAxis(*pad, *axis_type);
+                    ctx_mut(|ctx| ctx.gamepad_axes.insert(axis, *value));
+                    state.gamepad_axis(axis, *value);
+                }
+                GamepadEventType::Disconnected => {
+                    ctx_mut(|ctx| {
+                        let held: Vec<GamepadButton> = ctx
+                            .gamepad_buttons
+                            .pressed
+                            .iter()
+                            .copied()
+                            .filter(|button| button.0 == *pad)
+                            .collect();
+                        for button in held {
+                            ctx.gamepad_buttons.release(button);
+                        }
+                        ctx.gamepad_axes.retain(|axis, _| axis.0 != *pad);
+                    });
+                }
+                GamepadEventType::Connected => {}
+            }
+        }
+    }
+
     // Cursor move
     if !cursor_motion.is_empty() {
         for moved in cursor_motion.iter() {
@@ -1,31 +1,403 @@
+use std::{collections::HashMap, panic::Location};
+
+use bevy::prelude::*;
 use lockfree::prelude::Queue;
 use once_cell::sync::Lazy;
 
+/// Identifies a draw call so that the shape it produces can be matched to the
+/// same bevy entity across frames. Every `draw_*` call is keyed by its
+/// source location, plus an explicit `index` for the `*_indexed` variants (see
+/// the note on [`draw_rect`]).
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct RenderId {
     line: u32,
     col: u32,
+    index: u64,
+    file: &'static str,
 }
 
 impl RenderId {
-    pub fn new(a: u32, b: u32) -> Self {
-        RenderId { line: a, col: b }
+    #[track_caller]
+    fn here() -> Self {
+        RenderId::here_indexed(0)
+    }
+    #[track_caller]
+    fn here_indexed(index: u64) -> Self {
+        let loc = Location::caller();
+        RenderId {
+            line: loc.line(),
+            col: loc.column(),
+            index,
+            file: loc.file(),
+        }
     }
-}
-
-#[macro_export]
-macro_rules! id {
-    () => {
-        crate::RenderId::new(line!(), column!())
-    };
 }
 
 static RENDER_QUEUE: Lazy<Queue<RenderObject>> = Lazy::new(Default::default);
 
-enum RenderObject {}
+enum RenderObject {
+    Rect {
+        id: RenderId,
+        pos: Vec2,
+        size: Vec2,
+        color: Color,
+    },
+    Circle {
+        id: RenderId,
+        center: Vec2,
+        radius: f32,
+        color: Color,
+    },
+    Line {
+        id: RenderId,
+        a: Vec2,
+        b: Vec2,
+        thickness: f32,
+        color: Color,
+    },
+    Text {
+        id: RenderId,
+        pos: Vec2,
+        text: String,
+        size: f32,
+        color: Color,
+    },
+    Sprite {
+        id: RenderId,
+        handle: Handle<Image>,
+        pos: Vec2,
+    },
+}
 
 impl RenderObject {
+    fn id(&self) -> RenderId {
+        match self {
+            RenderObject::Rect { id, .. }
+            | RenderObject::Circle { id, .. }
+            | RenderObject::Line { id, .. }
+            | RenderObject::Text { id, .. }
+            | RenderObject::Sprite { id, .. } => *id,
+        }
+    }
+    fn kind(&self) -> RenderKind {
+        match self {
+            RenderObject::Rect { .. } => RenderKind::Rect,
+            RenderObject::Circle { .. } => RenderKind::Circle,
+            RenderObject::Line { .. } => RenderKind::Line,
+            RenderObject::Text { .. } => RenderKind::Text,
+            RenderObject::Sprite { .. } => RenderKind::Sprite,
+        }
+    }
     fn push(self) {
         RENDER_QUEUE.push(self);
     }
 }
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RenderKind {
+    Rect,
+    Circle,
+    Line,
+    Text,
+    Sprite,
+}
+
+fn rect(id: RenderId, pos: Vec2, size: Vec2, color: Color) {
+    RenderObject::Rect {
+        id,
+        pos,
+        size,
+        color,
+    }
+    .push();
+}
+
+fn circle(id: RenderId, center: Vec2, radius: f32, color: Color) {
+    RenderObject::Circle {
+        id,
+        center,
+        radius,
+        color,
+    }
+    .push();
+}
+
+fn line(id: RenderId, a: Vec2, b: Vec2, thickness: f32, color: Color) {
+    RenderObject::Line {
+        id,
+        a,
+        b,
+        thickness,
+        color,
+    }
+    .push();
+}
+
+fn text(id: RenderId, pos: Vec2, text: &str, size: f32, color: Color) {
+    RenderObject::Text {
+        id,
+        pos,
+        text: text.to_string(),
+        size,
+        color,
+    }
+    .push();
+}
+
+fn sprite(id: RenderId, handle: Handle<Image>, pos: Vec2) {
+    RenderObject::Sprite { id, handle, pos }.push();
+}
+
+/// Draw a filled axis-aligned rectangle with its center at `pos`.
+///
+/// Each shape is keyed by the source location of its `draw_*` call, so a single
+/// call site reused across frames reuses the same entity. A consequence is that
+/// calling `draw_rect` in a loop collapses every iteration onto one key, and
+/// only the last shape survives the frame. To draw many shapes from one call
+/// site, use [`draw_rect_indexed`] (and the other `*_indexed` variants) with a
+/// distinct index per iteration.
+#[track_caller]
+pub fn draw_rect(pos: Vec2, size: Vec2, color: Color) {
+    rect(RenderId::here(), pos, size, color);
+}
+
+/// Like [`draw_rect`], but with an explicit `index` so repeated calls from the
+/// same source location (e.g. inside a loop) each get their own entity.
+#[track_caller]
+pub fn draw_rect_indexed(index: u64, pos: Vec2, size: Vec2, color: Color) {
+    rect(RenderId::here_indexed(index), pos, size, color);
+}
+
+/// Draw a filled circle centered at `center`.
+#[track_caller]
+pub fn draw_circle(center: Vec2, radius: f32, color: Color) {
+    circle(RenderId::here(), center, radius, color);
+}
+
+/// Like [`draw_circle`], but with an explicit `index` for use in a loop.
+#[track_caller]
+pub fn draw_circle_indexed(index: u64, center: Vec2, radius: f32, color: Color) {
+    circle(RenderId::here_indexed(index), center, radius, color);
+}
+
+/// Draw a line segment from `a` to `b`.
+#[track_caller]
+pub fn draw_line(a: Vec2, b: Vec2, thickness: f32, color: Color) {
+    line(RenderId::here(), a, b, thickness, color);
+}
+
+/// Like [`draw_line`], but with an explicit `index` for use in a loop.
+#[track_caller]
+pub fn draw_line_indexed(index: u64, a: Vec2, b: Vec2, thickness: f32, color: Color) {
+    line(RenderId::here_indexed(index), a, b, thickness, color);
+}
+
+/// Draw a line of text with its top-left corner at `pos`.
+#[track_caller]
+pub fn draw_text(pos: Vec2, string: &str, size: f32, color: Color) {
+    text(RenderId::here(), pos, string, size, color);
+}
+
+/// Like [`draw_text`], but with an explicit `index` for use in a loop.
+#[track_caller]
+pub fn draw_text_indexed(index: u64, pos: Vec2, string: &str, size: f32, color: Color) {
+    text(RenderId::here_indexed(index), pos, string, size, color);
+}
+
+/// Draw a sprite from an image handle with its center at `pos`.
+#[track_caller]
+pub fn draw_sprite(handle: Handle<Image>, pos: Vec2) {
+    sprite(RenderId::here(), handle, pos);
+}
+
+/// Like [`draw_sprite`], but with an explicit `index` for use in a loop.
+#[track_caller]
+pub fn draw_sprite_indexed(index: u64, handle: Handle<Image>, pos: Vec2) {
+    sprite(RenderId::here_indexed(index), handle, pos);
+}
+
+/// Tracks which bevy entity currently backs each [`RenderId`] so that repeated
+/// `draw_*` calls from the same source location reuse the same entity instead
+/// of spawning a fresh one every frame.
+#[derive(Default)]
+pub(crate) struct RenderState {
+    entities: HashMap<RenderId, (Entity, RenderKind)>,
+    /// Shared unit-radius circle mesh, scaled per call via its transform.
+    circle_mesh: Option<Handle<Mesh>>,
+    /// Per-[`RenderId`] circle materials, mutated in place so redrawing a circle
+    /// does not allocate a fresh material every frame.
+    circle_materials: HashMap<RenderId, Handle<ColorMaterial>>,
+    /// Font asset path configured via `Config::font`, if any.
+    font_path: Option<String>,
+    /// Whether the missing-font warning has already been emitted.
+    warned_missing_font: bool,
+}
+
+impl RenderState {
+    pub(crate) fn new(font_path: Option<String>) -> Self {
+        RenderState {
+            font_path,
+            ..Default::default()
+        }
+    }
+}
+
+/// Drain the render queue built up during [`EventHandler::draw`] and diff it
+/// against last frame's entities, reusing those whose `RenderId` and kind are
+/// unchanged and despawning the ones that were not redrawn.
+pub(crate) fn flush_render(
+    mut commands: Commands,
+    mut state: ResMut<RenderState>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    let font: Option<Handle<Font>> = state
+        .font_path
+        .clone()
+        .map(|path| asset_server.load(path.as_str()));
+    let mut seen = HashMap::new();
+    while let Some(object) = RENDER_QUEUE.pop() {
+        let id = object.id();
+        let kind = object.kind();
+        // Reuse an entity already claimed by this id *this* frame before falling
+        // back to last frame's; otherwise two draws sharing one id would each
+        // spawn a live entity and all but the last would leak.
+        let reuse = seen
+            .get(&id)
+            .or_else(|| state.entities.get(&id))
+            .filter(|(_, prev)| *prev == kind)
+            .map(|(entity, _)| *entity);
+        let entity = reuse.unwrap_or_else(|| commands.spawn().id());
+        match object {
+            RenderObject::Rect {
+                pos, size, color, ..
+            } => {
+                commands.entity(entity).insert_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(size),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(pos.x, pos.y, 0.0),
+                    ..Default::default()
+                });
+            }
+            RenderObject::Circle {
+                center,
+                radius,
+                color,
+                ..
+            } => {
+                let mesh = state
+                    .circle_mesh
+                    .get_or_insert_with(|| meshes.add(Mesh::from(shape::Circle::new(1.0))))
+                    .clone();
+                let material = match state.circle_materials.get(&id) {
+                    Some(handle) => {
+                        if let Some(material) = materials.get_mut(handle) {
+                            material.color = color;
+                        }
+                        handle.clone()
+                    }
+                    None => {
+                        let handle = materials.add(ColorMaterial::from(color));
+                        state.circle_materials.insert(id, handle.clone());
+                        handle
+                    }
+                };
+                commands.entity(entity).insert_bundle(ColorMesh2dBundle {
+                    mesh: mesh.into(),
+                    material,
+                    transform: Transform {
+                        translation: center.extend(0.0),
+                        scale: Vec3::splat(radius),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+            }
+            RenderObject::Line {
+                a,
+                b,
+                thickness,
+                color,
+                ..
+            } => {
+                let delta = b - a;
+                let mid = a + delta / 2.0;
+                let angle = delta.y.atan2(delta.x);
+                commands.entity(entity).insert_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(vec2(delta.length(), thickness)),
+                        ..Default::default()
+                    },
+                    transform: Transform {
+                        translation: mid.extend(0.0),
+                        rotation: Quat::from_rotation_z(angle),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+            }
+            RenderObject::Text {
+                pos,
+                text,
+                size,
+                color,
+                ..
+            } => {
+                let font = match &font {
+                    Some(font) => font.clone(),
+                    None => {
+                        if !state.warned_missing_font {
+                            warn!(
+                                "draw_text called but no font is configured; \
+                                 set a font with Config::font to render text"
+                            );
+                            state.warned_missing_font = true;
+                        }
+                        continue;
+                    }
+                };
+                commands.entity(entity).insert_bundle(Text2dBundle {
+                    text: Text::with_section(
+                        text,
+                        TextStyle {
+                            font,
+                            font_size: size,
+                            color,
+                        },
+                        Default::default(),
+                    ),
+                    transform: Transform::from_xyz(pos.x, pos.y, 0.0),
+                    ..Default::default()
+                });
+            }
+            RenderObject::Sprite { handle, pos, .. } => {
+                commands.entity(entity).insert_bundle(SpriteBundle {
+                    texture: handle,
+                    transform: Transform::from_xyz(pos.x, pos.y, 0.0),
+                    ..Default::default()
+                });
+            }
+        }
+        seen.insert(id, (entity, kind));
+    }
+
+    // Despawn anything that was not redrawn this frame.
+    let stale: Vec<RenderId> = state
+        .entities
+        .keys()
+        .copied()
+        .filter(|id| !seen.contains_key(id))
+        .collect();
+    for id in stale {
+        if let Some((entity, _)) = state.entities.remove(&id) {
+            commands.entity(entity).despawn();
+        }
+        state.circle_materials.remove(&id);
+    }
+    state.entities = seen;
+}